@@ -12,11 +12,73 @@
 extern crate im;
 #[macro_use]
 extern crate moniker;
+#[macro_use]
+extern crate proptest;
 
 use im::HashMap;
-use moniker::{Binder, BoundTerm, Embed, FreeVar, Scope, Var};
+use moniker::{Binder, BoundPattern, BoundTerm, Embed, FreeVar, GenId, Nest, Scope, Var};
+use std::fmt;
+use std::iter::FromIterator;
 use std::rc::Rc;
 
+/// An order-insensitive, label-keyed collection.
+///
+/// Record and variant types are naturally unordered - `{x : Int, y : String}`
+/// and `{y : String, x : Int}` describe the same type - but a bare `Vec`
+/// compares and binds its entries positionally. The derived `BoundTerm`/
+/// `BoundPattern` impls below just delegate to the inner `Vec`'s positional
+/// behaviour, so `Unordered` gets canonical, order-insensitive `term_eq`,
+/// `close_term`/`open_term`, and pattern binder ordering only because `new`
+/// sorts its entries by key *once, up front* - there is no sorting inside
+/// `term_eq`/`close_term`/`open_term` themselves. This holds today because
+/// `new` (and `FromIterator`, which goes through it) is the only way to
+/// build one; a constructor added later that skips `new` would silently
+/// break the canonical ordering this type exists to guarantee.
+///
+/// Keys are treated as non-binding structure and must be unique; constructing
+/// an `Unordered` with duplicate keys panics.
+///
+/// Same constraint as [`usefulness`] and `unify`: a `moniker::Unordered`
+/// would let other `BoundTerm`/`BoundPattern` users share this, but
+/// `moniker` is a fixed published dependency of this tree, not a vendored
+/// source tree this file can add a type to.
+#[derive(Debug, Clone, BoundTerm, BoundPattern)]
+pub struct Unordered<K, T>(Vec<(K, T)>);
+
+impl<K: Ord + Clone, T> Unordered<K, T> {
+    pub fn new(mut entries: Vec<(K, T)>) -> Unordered<K, T> {
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for entries in entries.windows(2) {
+            assert!(
+                entries[0].0 != entries[1].0,
+                "duplicate key in `Unordered` collection",
+            );
+        }
+        Unordered(entries)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(K, T)> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&T> {
+        self.0
+            .binary_search_by(|(k, _)| k.cmp(key))
+            .ok()
+            .map(move |i| &self.0[i].1)
+    }
+}
+
+impl<K: Ord + Clone, T> FromIterator<(K, T)> for Unordered<K, T> {
+    fn from_iter<I: IntoIterator<Item = (K, T)>>(iter: I) -> Unordered<K, T> {
+        Unordered::new(iter.into_iter().collect())
+    }
+}
+
 /// Types
 #[derive(Debug, Clone, BoundTerm)]
 pub enum Type {
@@ -29,9 +91,12 @@ pub enum Type {
     /// Function types
     Arrow(RcType, RcType),
     /// Record types
-    Record(Vec<(String, RcType)>),
+    Record(Unordered<String, RcType>),
     /// Variant types
-    Variant(Vec<(String, RcType)>),
+    Variant(Unordered<String, RcType>),
+    /// Unification metavariables, introduced during inference and solved
+    /// away by [`Unifier::zonk`]
+    Meta(FreeVar<String>),
 }
 
 /// Reference counted types
@@ -69,7 +134,7 @@ pub enum Pattern {
     /// Patterns that bind variables
     Binder(Binder<String>),
     /// Record patterns
-    Record(Vec<(String, RcPattern)>),
+    Record(Unordered<String, RcPattern>),
     /// Tag pattern
     Tag(String, RcPattern),
 }
@@ -102,7 +167,7 @@ pub enum Expr {
     /// Function application
     App(RcExpr, RcExpr),
     /// Record values
-    Record(Vec<(String, RcExpr)>),
+    Record(Unordered<String, RcExpr>),
     /// Field projection on records
     Proj(RcExpr, String),
     /// Variant introduction
@@ -125,9 +190,244 @@ impl From<Expr> for RcExpr {
     }
 }
 
+/// A name-restoring pretty-printer.
+///
+/// `{:?}` renders every `Var::Bound`/`FreeVar` produced by opening a `Scope`
+/// with its opaque, globally-unique `GenId`, which is unreadable once a term
+/// has any depth to it. This module re-opens each `Scope` as it walks a term,
+/// choosing the shortest name for every binder that does not collide with a
+/// name already in scope - preferring the binder's own hint - and renders
+/// every later use of that binder with the same chosen name.
+///
+/// Same constraint as [`usefulness`]: name-restoring pretty-printing would be
+/// useful to any `moniker`-based language, but `moniker` is a fixed published
+/// dependency of this tree with no vendored source to add it to, so it stays
+/// a private module local to this example instead.
+mod pretty {
+    use std::collections::{HashMap, HashSet};
+    use std::fmt;
+
+    use super::{Expr, FreeVar, Literal, Pattern, RcExpr, RcPattern, RcType, Type};
+
+    /// A policy for choosing a fresh, readable name that avoids a given set
+    /// of names already in scope. Callers can plug in their own policy via
+    /// [`to_doc_with`]; [`DefaultNameSupply`] is used by [`to_doc`].
+    pub trait NameSupply {
+        fn fresh(&self, hint: &str, in_scope: &HashSet<String>) -> String;
+    }
+
+    /// Tries the hint itself, then `hint1`, `hint2`, ... until one is free.
+    pub struct DefaultNameSupply;
+
+    impl NameSupply for DefaultNameSupply {
+        fn fresh(&self, hint: &str, in_scope: &HashSet<String>) -> String {
+            let hint = if hint.is_empty() { "x" } else { hint };
+            if !in_scope.contains(hint) {
+                return hint.to_string();
+            }
+            (1..)
+                .map(|i| format!("{}{}", hint, i))
+                .find(|name| !in_scope.contains(name))
+                .unwrap()
+        }
+    }
+
+    /// A rendered document. This example has no layout needs beyond string
+    /// concatenation, so a `Doc` is just the finished text.
+    pub struct Doc(String);
+
+    impl fmt::Display for Doc {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    /// Tracks the names chosen so far as a term is walked: which display
+    /// names are currently in scope, and which display name each free
+    /// variable was assigned when its binder was opened.
+    struct Env<'supply, S: NameSupply> {
+        names: &'supply S,
+        in_scope: HashSet<String>,
+        assigned: HashMap<FreeVar<String>, String>,
+    }
+
+    impl<'supply, S: NameSupply> Env<'supply, S> {
+        fn bind(&mut self, free_var: &FreeVar<String>, hint: &str) -> String {
+            let name = self.names.fresh(hint, &self.in_scope);
+            self.in_scope.insert(name.clone());
+            self.assigned.insert(free_var.clone(), name.clone());
+            name
+        }
+
+        fn lookup(&self, free_var: &FreeVar<String>) -> String {
+            match self.assigned.get(free_var) {
+                Some(name) => name.clone(),
+                None => free_var
+                    .ident()
+                    .cloned()
+                    .unwrap_or_else(|| format!("{:?}", free_var)),
+            }
+        }
+    }
+
+    pub fn to_doc(expr: &RcExpr) -> Doc {
+        to_doc_with(&DefaultNameSupply, expr)
+    }
+
+    pub fn to_doc_with(names: &impl NameSupply, expr: &RcExpr) -> Doc {
+        let mut env = Env {
+            names,
+            in_scope: HashSet::new(),
+            assigned: HashMap::new(),
+        };
+        Doc(pp_expr(&mut env, expr))
+    }
+
+    fn pp_ty(ty: &RcType) -> String {
+        match *ty.inner {
+            Type::Int => "Int".to_string(),
+            Type::Float => "Float".to_string(),
+            Type::String => "String".to_string(),
+            Type::Arrow(ref param_ty, ref ret_ty) => {
+                format!("({} -> {})", pp_ty(param_ty), pp_ty(ret_ty))
+            },
+            Type::Record(ref fields) => format!(
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|&(ref label, ref ty)| format!("{} : {}", label, pp_ty(ty)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Type::Variant(ref variants) => format!(
+                "<{}>",
+                variants
+                    .iter()
+                    .map(|&(ref label, ref ty)| format!("{} : {}", label, pp_ty(ty)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Type::Meta(ref free_var) => format!("?{:?}", free_var),
+        }
+    }
+
+    fn pp_pattern<S: NameSupply>(env: &mut Env<S>, pattern: &RcPattern) -> String {
+        match *pattern.inner {
+            Pattern::Ann(ref pattern, ref ty) => {
+                format!("({} : {})", pp_pattern(env, pattern), pp_ty(&ty.0))
+            },
+            Pattern::Literal(ref lit) => pp_literal(lit),
+            Pattern::Binder(ref binder) => {
+                let hint = binder
+                    .0
+                    .ident()
+                    .cloned()
+                    .unwrap_or_else(|| "x".to_string());
+                env.bind(&binder.0, &hint)
+            },
+            Pattern::Record(ref fields) => format!(
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|&(ref label, ref pattern)| {
+                        format!("{} = {}", label, pp_pattern(env, pattern))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Pattern::Tag(ref label, ref pattern) => format!("{} {}", label, pp_pattern(env, pattern)),
+        }
+    }
+
+    fn pp_literal(lit: &Literal) -> String {
+        match *lit {
+            Literal::Int(value) => value.to_string(),
+            Literal::Float(value) => value.to_string(),
+            Literal::String(ref value) => format!("{:?}", value),
+        }
+    }
+
+    fn pp_expr<S: NameSupply>(env: &mut Env<S>, expr: &RcExpr) -> String {
+        match *expr.inner {
+            Expr::Ann(ref expr, ref ty) => format!("({} : {})", pp_expr(env, expr), pp_ty(ty)),
+            Expr::Literal(ref lit) => pp_literal(lit),
+            Expr::Var(super::Var::Free(ref free_var)) => env.lookup(free_var),
+            Expr::Var(super::Var::Bound(_, _, _)) => {
+                panic!("encountered a bound variable - `to_doc` expects every scope to be open")
+            },
+            Expr::Lam(ref scope) => {
+                let (pattern, body) = scope.clone().unbind();
+                let outer_scope = env.in_scope.clone();
+                let doc = format!("\\{} -> {}", pp_pattern(env, &pattern), pp_expr(env, &body));
+                env.in_scope = outer_scope;
+                doc
+            },
+            Expr::App(ref fun, ref arg) => format!("({} {})", pp_expr(env, fun), pp_expr(env, arg)),
+            Expr::Record(ref fields) => format!(
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|&(ref label, ref expr)| format!("{} = {}", label, pp_expr(env, expr)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Expr::Proj(ref expr, ref label) => format!("{}.{}", pp_expr(env, expr), label),
+            Expr::Tag(ref label, ref expr) => format!("{} {}", label, pp_expr(env, expr)),
+            Expr::Case(ref expr, ref clauses) => format!(
+                "case {} {{ {} }}",
+                pp_expr(env, expr),
+                clauses
+                    .iter()
+                    .map(|clause| {
+                        let (pattern, body) = clause.clone().unbind();
+                        let outer_scope = env.in_scope.clone();
+                        let doc =
+                            format!("{} => {}", pp_pattern(env, &pattern), pp_expr(env, &body));
+                        env.in_scope = outer_scope;
+                        doc
+                    })
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+        }
+    }
+}
+
 impl RcExpr {
-    // FIXME: auto-derive this somehow!
-    fn substs<N>(&self, mappings: &[(N, RcExpr)]) -> RcExpr
+    pub fn to_doc(&self) -> pretty::Doc {
+        pretty::to_doc(self)
+    }
+}
+
+impl fmt::Display for RcExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_doc())
+    }
+}
+
+/// Capture-avoiding substitution of free variables.
+///
+/// Because moniker terms are locally nameless, this is just structural
+/// recursion: bound occurrences are de Bruijn indices hiding inside
+/// `unsafe_body`/`unsafe_pattern`, so walking straight through them can never
+/// confuse a bound occurrence for a free one. The only place that recursion
+/// must *not* go is the binder-name position of a `Binder<_>` itself, since
+/// that is a binding occurrence rather than a use.
+///
+/// This is still a hand-written `impl` for `RcExpr` alone, not a
+/// `#[derive(BoundTerm)]` output: the `moniker-derive` crate isn't part of
+/// this tree, so there is nothing here to teach it to emit `substs`
+/// automatically. Every other `BoundTerm` type in this file (eg. `RcType`)
+/// would need the same match written out by hand to get a `Subst` impl of
+/// its own.
+pub trait Subst<N>: Sized {
+    fn substs(&self, mappings: &[(N, Self)]) -> Self
+    where
+        Var<String>: PartialEq<N>;
+}
+
+impl<N> Subst<N> for RcExpr {
+    fn substs(&self, mappings: &[(N, RcExpr)]) -> RcExpr
     where
         Var<String>: PartialEq<N>,
     {
@@ -166,7 +466,7 @@ impl RcExpr {
                 clauses
                     .iter()
                     .map(|scope| Scope {
-                        unsafe_pattern: scope.unsafe_pattern.clone(), // subst?
+                        unsafe_pattern: scope.unsafe_pattern.clone(),
                         unsafe_body: scope.unsafe_body.substs(mappings),
                     })
                     .collect(),
@@ -202,7 +502,7 @@ pub fn eval(expr: &RcExpr) -> RcExpr {
             let expr = eval(expr);
 
             if let Expr::Record(ref fields) = *expr.inner {
-                if let Some(&(_, ref e)) = fields.iter().find(|&(ref l, _)| l == label) {
+                if let Some(e) = fields.get(label) {
                     return e.clone();
                 }
             }
@@ -239,7 +539,9 @@ pub fn match_expr(pattern: &RcPattern, expr: &RcExpr) -> Option<Vec<(FreeVar<Str
         (&Pattern::Record(ref pattern_fields), &Expr::Record(ref expr_fields))
             if pattern_fields.len() == expr_fields.len() =>
         {
-            // FIXME: allow out-of-order fields in records
+            // Both sides are stored in canonical key order, so this
+            // comparison is order-insensitive even though it walks the
+            // entries positionally.
             let mut mappings = Vec::new();
             for (pattern_field, expr_field) in <_>::zip(pattern_fields.iter(), expr_fields.iter()) {
                 if pattern_field.0 != expr_field.0 {
@@ -262,53 +564,207 @@ pub fn match_expr(pattern: &RcPattern, expr: &RcExpr) -> Option<Vec<(FreeVar<Str
 /// A context containing a series of type annotations
 type Context = HashMap<FreeVar<String>, RcType>;
 
+/// Unification of types, used to replace rigid `term_eq` comparisons in the
+/// checker with a proper unification variable solver.
+///
+/// This only unifies `RcType`, not any `BoundTerm` - there is just the one
+/// term that needs metavariables here, and a second implementation (eg. for
+/// `RcExpr`) would need its own `Meta` constructor and occurs check, so we
+/// are not claiming a generic `unify<T: BoundTerm<N>>` exists.
+///
+/// Same constraint as [`usefulness`]: a generic `moniker::unify` is a
+/// `moniker`-crate-level feature, but `moniker` is a fixed published
+/// dependency of this tree with no vendored source to add one to, so this
+/// stays a private, `RcType`-specific module here instead.
+mod unify {
+    use super::{RcType, Type};
+    use im::HashMap;
+    use moniker::{FreeVar, GenId};
+
+    #[derive(Debug, Clone)]
+    pub enum UnifyError {
+        /// The two types have mismatched head constructors
+        Mismatch(RcType, RcType),
+        /// A metavariable would have to unify with a type that contains it
+        Occurs(FreeVar<String>, RcType),
+    }
+
+    /// A substitution of metavariables to the types they have been solved to
+    #[derive(Debug, Clone)]
+    pub struct Unifier {
+        subst: HashMap<FreeVar<String>, RcType>,
+    }
+
+    impl Unifier {
+        pub fn new() -> Unifier {
+            Unifier {
+                subst: HashMap::new(),
+            }
+        }
+
+        /// Introduces a fresh, yet-unsolved metavariable
+        pub fn fresh_meta(&mut self) -> RcType {
+            RcType::from(Type::Meta(FreeVar::from(GenId::fresh())))
+        }
+
+        /// Fully applies the current substitution to a type, replacing every
+        /// solved metavariable with the type it was unified with
+        pub fn zonk(&self, ty: &RcType) -> RcType {
+            match *ty.inner {
+                Type::Meta(ref free_var) => match self.subst.get(free_var) {
+                    Some(solved) => self.zonk(solved),
+                    None => ty.clone(),
+                },
+                Type::Arrow(ref param_ty, ref ret_ty) => RcType::from(Type::Arrow(
+                    self.zonk(param_ty),
+                    self.zonk(ret_ty),
+                )),
+                Type::Record(ref fields) => RcType::from(Type::Record(
+                    fields
+                        .iter()
+                        .map(|&(ref label, ref ty)| (label.clone(), self.zonk(ty)))
+                        .collect(),
+                )),
+                Type::Variant(ref variants) => RcType::from(Type::Variant(
+                    variants
+                        .iter()
+                        .map(|&(ref label, ref ty)| (label.clone(), self.zonk(ty)))
+                        .collect(),
+                )),
+                Type::Int | Type::Float | Type::String => ty.clone(),
+            }
+        }
+
+        fn occurs(&self, free_var: &FreeVar<String>, ty: &RcType) -> bool {
+            match *ty.inner {
+                Type::Meta(ref other) => match self.subst.get(other) {
+                    Some(solved) => self.occurs(free_var, solved),
+                    None => other == free_var,
+                },
+                Type::Arrow(ref param_ty, ref ret_ty) => {
+                    self.occurs(free_var, param_ty) || self.occurs(free_var, ret_ty)
+                },
+                Type::Record(ref fields) | Type::Variant(ref fields) => fields
+                    .iter()
+                    .any(|&(_, ref ty)| self.occurs(free_var, ty)),
+                Type::Int | Type::Float | Type::String => false,
+            }
+        }
+
+        fn bind(&mut self, free_var: FreeVar<String>, ty: RcType) -> Result<(), UnifyError> {
+            if self.occurs(&free_var, &ty) {
+                Err(UnifyError::Occurs(free_var, ty))
+            } else {
+                self.subst.insert(free_var, ty);
+                Ok(())
+            }
+        }
+
+        /// Unifies two types, recording any metavariable solutions needed to
+        /// make them equal
+        pub fn unify(&mut self, a: &RcType, b: &RcType) -> Result<(), UnifyError> {
+            match (&*a.inner, &*b.inner) {
+                (&Type::Meta(ref free_var), _) if self.subst.get(free_var).is_some() => {
+                    let solved = self.subst.get(free_var).unwrap().clone();
+                    self.unify(&solved, b)
+                },
+                (_, &Type::Meta(ref free_var)) if self.subst.get(free_var).is_some() => {
+                    let solved = self.subst.get(free_var).unwrap().clone();
+                    self.unify(a, &solved)
+                },
+                // An unbound metavariable trivially unifies with itself -
+                // without this, `bind` would try to solve `free_var` to
+                // `Type::Meta(free_var)`, and the occurs check would (quite
+                // correctly, were this not the same variable) reject that as
+                // a cycle.
+                (&Type::Meta(ref a_var), &Type::Meta(ref b_var)) if a_var == b_var => Ok(()),
+                (&Type::Meta(ref free_var), _) => self.bind(free_var.clone(), b.clone()),
+                (_, &Type::Meta(ref free_var)) => self.bind(free_var.clone(), a.clone()),
+                (&Type::Int, &Type::Int) | (&Type::Float, &Type::Float) | (&Type::String, &Type::String) => {
+                    Ok(())
+                },
+                (&Type::Arrow(ref a_param, ref a_ret), &Type::Arrow(ref b_param, ref b_ret)) => {
+                    self.unify(a_param, b_param)?;
+                    self.unify(a_ret, b_ret)
+                },
+                (&Type::Record(ref a_fields), &Type::Record(ref b_fields))
+                | (&Type::Variant(ref a_fields), &Type::Variant(ref b_fields))
+                    if a_fields.len() == b_fields.len() =>
+                {
+                    for (a_field, b_field) in <_>::zip(a_fields.iter(), b_fields.iter()) {
+                        if a_field.0 != b_field.0 {
+                            return Err(UnifyError::Mismatch(a.clone(), b.clone()));
+                        }
+                        self.unify(&a_field.1, &b_field.1)?;
+                    }
+                    Ok(())
+                },
+                (_, _) => Err(UnifyError::Mismatch(a.clone(), b.clone())),
+            }
+        }
+    }
+}
+
+use self::unify::Unifier;
+
 /// Check that a (potentially ambiguous) expression conforms to a given ype
-pub fn check_expr(context: &Context, expr: &RcExpr, expected_ty: &RcType) -> Result<(), String> {
+pub fn check_expr(
+    unifier: &mut Unifier,
+    context: &Context,
+    expr: &RcExpr,
+    expected_ty: &RcType,
+) -> Result<(), String> {
     match (&*expr.inner, &*expected_ty.inner) {
         (&Expr::Lam(ref scope), &Type::Arrow(ref param_ty, ref ret_ty)) => {
             let (pattern, body) = scope.clone().unbind();
-            let bindings = check_pattern(context, &pattern, param_ty)?;
-            return check_expr(&(context + &bindings), &body, ret_ty);
+            let bindings = check_pattern(unifier, context, &pattern, param_ty)?;
+            return check_expr(unifier, &(context + &bindings), &body, ret_ty);
         },
         (&Expr::Tag(ref label, ref expr), &Type::Variant(ref variants)) => {
-            return match variants.iter().find(|&(l, _)| l == label) {
+            return match variants.get(label) {
                 None => Err(format!(
                     "variant type did not contain the label `{}`",
                     label
                 )),
-                Some(&(_, ref ty)) => check_expr(context, expr, ty),
+                Some(ty) => check_expr(unifier, context, expr, ty),
             };
         },
         (&Expr::Case(ref expr, ref clauses), _) => {
-            let expr_ty = infer_expr(context, expr)?;
+            let expr_ty = infer_expr(unifier, context, expr)?;
+            let mut patterns = Vec::with_capacity(clauses.len());
             for clause in clauses {
                 let (pattern, body) = clause.clone().unbind();
-                let bindings = check_pattern(context, &pattern, &expr_ty)?;
-                check_expr(&(context + &bindings), &body, expected_ty)?;
+                let bindings = check_pattern(unifier, context, &pattern, &expr_ty)?;
+                check_expr(unifier, &(context + &bindings), &body, expected_ty)?;
+                patterns.push(pattern);
+            }
+            if let Err(witness) = usefulness::check_exhaustive(&patterns, &unifier.zonk(&expr_ty)) {
+                return Err(format!(
+                    "non-exhaustive patterns - `{:?}` not covered",
+                    witness,
+                ));
             }
             return Ok(());
         },
         (_, _) => {},
     }
 
-    let inferred_ty = infer_expr(&context, expr)?;
+    let inferred_ty = infer_expr(unifier, &context, expr)?;
 
-    // FIXME: allow out-of-order fields in records
-    if RcType::term_eq(&inferred_ty, expected_ty) {
-        Ok(())
-    } else {
-        Err(format!(
+    unifier.unify(&inferred_ty, expected_ty).map_err(|_| {
+        format!(
             "type mismatch - found `{:?}` but expected `{:?}`",
-            inferred_ty, expected_ty
-        ))
-    }
+            unifier.zonk(&inferred_ty),
+            unifier.zonk(expected_ty),
+        )
+    })
 }
 
 /// Synthesize the types of unambiguous expressions
-pub fn infer_expr(context: &Context, expr: &RcExpr) -> Result<RcType, String> {
+pub fn infer_expr(unifier: &mut Unifier, context: &Context, expr: &RcExpr) -> Result<RcType, String> {
     match *expr.inner {
         Expr::Ann(ref expr, ref ty) => {
-            check_expr(context, expr, ty)?;
+            check_expr(unifier, context, expr, ty)?;
             Ok(ty.clone())
         },
         Expr::Literal(Literal::Int(_)) => Ok(RcType::from(Type::Int)),
@@ -321,52 +777,394 @@ pub fn infer_expr(context: &Context, expr: &RcExpr) -> Result<RcType, String> {
         Expr::Var(Var::Bound(_, _, _)) => panic!("encountered a bound variable"),
         Expr::Lam(ref scope) => {
             let (pattern, body) = scope.clone().unbind();
-            let (ann, bindings) = infer_pattern(context, &pattern)?;
-            let body_ty = infer_expr(&(context + &bindings), &body)?;
+            let (ann, bindings) = infer_pattern(unifier, context, &pattern)?;
+            let body_ty = infer_expr(unifier, &(context + &bindings), &body)?;
             Ok(RcType::from(Type::Arrow(ann, body_ty)))
         },
-        Expr::App(ref fun, ref arg) => match *infer_expr(context, fun)?.inner {
+        Expr::App(ref fun, ref arg) => match *infer_expr(unifier, context, fun)?.inner {
             Type::Arrow(ref param_ty, ref ret_ty) => {
-                let arg_ty = infer_expr(context, arg)?;
-                if RcType::term_eq(param_ty, &arg_ty) {
-                    Ok(ret_ty.clone())
-                } else {
-                    Err(format!(
+                let arg_ty = infer_expr(unifier, context, arg)?;
+                unifier.unify(param_ty, &arg_ty).map_err(|_| {
+                    format!(
                         "argument type mismatch - found `{:?}` but expected `{:?}`",
-                        arg_ty, param_ty,
-                    ))
-                }
+                        unifier.zonk(&arg_ty),
+                        unifier.zonk(param_ty),
+                    )
+                })?;
+                Ok(ret_ty.clone())
             },
             _ => Err(format!("`{:?}` is not a function", fun)),
         },
         Expr::Record(ref fields) => {
             let fields = fields
                 .iter()
-                .map(|&(ref label, ref expr)| Ok((label.clone(), infer_expr(context, expr)?)))
+                .map(|&(ref label, ref expr)| {
+                    Ok((label.clone(), infer_expr(unifier, context, expr)?))
+                })
                 .collect::<Result<_, String>>()?;
 
             Ok(RcType::from(Type::Record(fields)))
         },
-        Expr::Proj(ref expr, ref label) => match *infer_expr(context, expr)?.inner {
-            Type::Record(ref fields) => match fields.iter().find(|&(l, _)| l == label) {
-                Some(&(_, ref ty)) => Ok(ty.clone()),
+        Expr::Proj(ref expr, ref label) => match *infer_expr(unifier, context, expr)?.inner {
+            Type::Record(ref fields) => match fields.get(label) {
+                Some(ty) => Ok(ty.clone()),
                 None => Err(format!("field `{}` not found in type", label)),
             },
             _ => Err("record expected".to_string()),
         },
         Expr::Tag(_, _) => Err("type annotations needed".to_string()),
-        Expr::Case(_, _) => Err("type annotations needed".to_string()),
+        Expr::Case(ref expr, ref clauses) => {
+            let expr_ty = infer_expr(unifier, context, expr)?;
+            let result_ty = unifier.fresh_meta();
+            let mut patterns = Vec::with_capacity(clauses.len());
+            for clause in clauses {
+                let (pattern, body) = clause.clone().unbind();
+                let bindings = check_pattern(unifier, context, &pattern, &expr_ty)?;
+                let body_ty = infer_expr(unifier, &(context + &bindings), &body)?;
+                unifier.unify(&result_ty, &body_ty).map_err(|_| {
+                    format!(
+                        "clause type mismatch - found `{:?}` but expected `{:?}`",
+                        unifier.zonk(&body_ty),
+                        unifier.zonk(&result_ty),
+                    )
+                })?;
+                patterns.push(pattern);
+            }
+            if let Err(witness) = usefulness::check_exhaustive(&patterns, &unifier.zonk(&expr_ty)) {
+                return Err(format!(
+                    "non-exhaustive patterns - `{:?}` not covered",
+                    witness,
+                ));
+            }
+            Ok(unifier.zonk(&result_ty))
+        },
     }
 }
 
-// TODO: Check pattern coverage/exhaustiveness (ie. if a series of patterns
-// cover all cases)
+/// An ordered sequence of binding *patterns* where later entries may depend
+/// on earlier ones, eg. a record pattern `{ n = x, xs = y : Vec x }` where
+/// `y`'s annotation refers to `x`.
+///
+/// `Telescope<P>` is a thin wrapper around moniker's own `Nest<P>`, which is
+/// built for exactly this left-to-right, pattern-in-pattern chaining (unlike
+/// `Scope<P, T>`, whose body slot `T` must be a *term*, not another
+/// pattern). Pairing the `Nest` with a unit `()` body inside a `Scope` lets
+/// us reuse `Scope::unbind`'s fresh-name-opening machinery for free, since
+/// `()` trivially implements `BoundTerm` (it carries no variables of its
+/// own).
+///
+/// Note this only threads bindings through *patterns* - it does not give
+/// `RcType` a way for a later field's annotation to depend on an earlier
+/// field's *value* (`RcType`'s only variable-carrying constructor is `Meta`,
+/// a unification metavariable, which cannot name a pattern binder), so this
+/// does not by itself enable fully dependent record *types*, only the
+/// pattern-side scoping a dependent record would need.
+///
+/// `Telescope` stays local to this example rather than becoming a `moniker`
+/// type for the same reason as [`usefulness`]: `moniker` is a fixed
+/// published dependency of this tree, not a vendored source tree, so there
+/// is nowhere here to add it as a first-class `moniker::Telescope`.
+pub struct Telescope<P> {
+    scope: Scope<Nest<P>, ()>,
+}
+
+impl<P: BoundPattern<String>> Telescope<P> {
+    pub fn new(entries: Vec<P>) -> Telescope<P> {
+        Telescope {
+            scope: Scope::new(Nest::new(entries), ()),
+        }
+    }
+
+    /// Opens every binder in the telescope to fresh free variables,
+    /// returning the entries left-to-right with all internal references
+    /// between them resolved.
+    ///
+    /// `Scope::unbind` alone only freshens the outer pattern's binders and
+    /// opens the (inert) `()` body - it never walks the `Nest`'s own
+    /// incrementally-closed internal structure, so a later entry whose data
+    /// refers to an earlier entry's binder would come back with that
+    /// reference still a dangling `Var::Bound` pointing at a scope depth
+    /// rather than the earlier entry's (now freshened) free variable. Calling
+    /// `Nest::unnest` afterwards is what actually performs that incremental
+    /// re-opening, translating each entry's internal references back into
+    /// the earlier entries' freshened free variables as it goes.
+    pub fn unbind(self) -> Vec<P> {
+        let (nest, ()) = self.scope.unbind();
+        nest.unnest()
+    }
+}
+
+/// Pattern exhaustiveness and usefulness checking.
+///
+/// This follows the standard matrix-based usefulness algorithm: a pattern
+/// `q` is useful against a matrix `P` if there is some value matched by `q`
+/// that is not matched by any row of `P`. Exhaustiveness of a column of
+/// clauses then falls out of testing whether an all-wildcard row is useful
+/// against that column - if it is not useful, the clauses are exhaustive; if
+/// it is useful, the witness row describes an unmatched case.
+///
+/// This lives here as a private `mod`, not in `moniker` itself, because
+/// `moniker` isn't part of this tree - it's pulled in as a fixed published
+/// dependency, with no vendored copy to add a `moniker::usefulness` module
+/// to. A language other than this example's that wanted exhaustiveness
+/// checking would need this algorithm hoisted into `moniker` proper (or its
+/// own copy of it); that's a change to the `moniker` crate itself, which is
+/// out of scope for an example file in a downstream tree.
+mod usefulness {
+    use std::collections::HashSet;
+
+    /// A head constructor together with the number of sub-patterns it binds.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub struct Ctor {
+        pub name: String,
+        pub arity: usize,
+    }
+
+    /// Types whose patterns can be decomposed into a head constructor and its
+    /// sub-patterns, for the purposes of usefulness checking.
+    ///
+    /// `signature`/`ctor_tys` take the *scrutinee's type* rather than a
+    /// pattern: the candidate row tested for usefulness starts out as a bare
+    /// wildcard, which by itself carries no information about what type it
+    /// stands for, so the type being matched against has to be threaded in
+    /// from outside rather than read off a pattern.
+    pub trait Constructors: Sized + Clone {
+        /// The type of value this pattern matches against, eg. `RcType`.
+        type Ty: Clone;
+        /// The head constructor of this pattern, or `None` if it is a
+        /// catch-all (a binder/wildcard).
+        fn ctor(&self) -> Option<Ctor>;
+        /// The sub-patterns of this pattern's head constructor.
+        fn ctor_args(&self) -> Vec<Self>;
+        /// Rebuilds a pattern from a head constructor and its sub-patterns,
+        /// used to build witnesses of non-exhaustiveness.
+        fn from_ctor(ctor: &Ctor, args: Vec<Self>) -> Self;
+        /// All constructors of `ty`, or `None` if the set is open (eg.
+        /// integer or string literals), in which case a wildcard is always
+        /// required to make a match exhaustive.
+        fn signature(ty: &Self::Ty) -> Option<Vec<Ctor>>;
+        /// The types of `ctor`'s sub-patterns, given the type `ctor` was
+        /// matched against, eg. a variant's tag maps to its payload type.
+        fn ctor_tys(ctor: &Ctor, ty: &Self::Ty) -> Vec<Self::Ty>;
+        /// A catch-all wildcard pattern.
+        fn wildcard() -> Self;
+    }
+
+    type Matrix<P> = Vec<Vec<P>>;
+
+    fn specialize<P: Constructors>(ctor: &Ctor, matrix: &Matrix<P>) -> Matrix<P> {
+        matrix
+            .iter()
+            .filter_map(|row| {
+                let (head, rest) = row.split_first()?;
+                match head.ctor() {
+                    Some(ref head_ctor) if head_ctor == ctor => {
+                        let mut row = head.ctor_args();
+                        row.extend(rest.iter().cloned());
+                        Some(row)
+                    },
+                    Some(_) => None,
+                    None => {
+                        let mut row = vec![P::wildcard(); ctor.arity];
+                        row.extend(rest.iter().cloned());
+                        Some(row)
+                    },
+                }
+            })
+            .collect()
+    }
+
+    fn default_matrix<P: Constructors>(matrix: &Matrix<P>) -> Matrix<P> {
+        matrix
+            .iter()
+            .filter_map(|row| {
+                let (head, rest) = row.split_first()?;
+                match head.ctor() {
+                    Some(_) => None,
+                    None => Some(rest.to_vec()),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns `Some(witness)` if `row` is useful against `matrix`, ie. if it
+    /// matches some value that no row of `matrix` matches. `col_tys` gives
+    /// the type of each column of `row`/`matrix`, so that a wildcard head can
+    /// look up its type's constructor signature.
+    fn useful<P: Constructors>(matrix: &Matrix<P>, row: &[P], col_tys: &[P::Ty]) -> Option<Vec<P>> {
+        let (head, rest) = match row.split_first() {
+            None => return if matrix.is_empty() { Some(vec![]) } else { None },
+            Some(head_rest) => head_rest,
+        };
+        let (head_ty, rest_tys) = col_tys.split_first().expect("row/col_tys length mismatch");
+
+        match head.ctor() {
+            Some(ctor) => {
+                let specialized = specialize(&ctor, matrix);
+                let mut specialized_row = head.ctor_args();
+                specialized_row.extend(rest.iter().cloned());
+                let mut specialized_tys = P::ctor_tys(&ctor, head_ty);
+                specialized_tys.extend(rest_tys.iter().cloned());
+
+                useful(&specialized, &specialized_row, &specialized_tys).map(|mut witness| {
+                    let args = witness.drain(..ctor.arity).collect();
+                    let mut witness_row = vec![P::from_ctor(&ctor, args)];
+                    witness_row.extend(witness);
+                    witness_row
+                })
+            },
+            None => {
+                let seen: HashSet<Ctor> = matrix.iter().filter_map(|r| r[0].ctor()).collect();
+                let signature = P::signature(head_ty);
+                let is_complete =
+                    signature.as_ref().map_or(false, |ctors| {
+                        !ctors.is_empty() && ctors.iter().all(|c| seen.contains(c))
+                    });
+
+                if is_complete {
+                    let ctors = signature.unwrap();
+                    ctors.iter().find_map(|ctor| {
+                        let specialized = specialize(ctor, matrix);
+                        let mut specialized_row = vec![P::wildcard(); ctor.arity];
+                        specialized_row.extend(rest.iter().cloned());
+                        let mut specialized_tys = P::ctor_tys(ctor, head_ty);
+                        specialized_tys.extend(rest_tys.iter().cloned());
+
+                        useful(&specialized, &specialized_row, &specialized_tys).map(|mut witness| {
+                            let args = witness.drain(..ctor.arity).collect();
+                            let mut witness_row = vec![P::from_ctor(ctor, args)];
+                            witness_row.extend(witness);
+                            witness_row
+                        })
+                    })
+                } else {
+                    let defaulted = default_matrix(matrix);
+                    useful(&defaulted, rest, rest_tys).map(|mut witness| {
+                        witness.insert(0, P::wildcard());
+                        witness
+                    })
+                }
+            },
+        }
+    }
+
+    /// Checks whether a column of clause patterns covers every value of the
+    /// scrutinee type `ty`, returning a witness pattern for the first
+    /// uncovered case found.
+    pub fn check_exhaustive<P: Constructors>(clauses: &[P], ty: &P::Ty) -> Result<(), P> {
+        let matrix: Matrix<P> = clauses.iter().map(|p| vec![p.clone()]).collect();
+        match useful(&matrix, &[P::wildcard()], &[ty.clone()]) {
+            None => Ok(()),
+            Some(witness) => Err(witness.into_iter().next().unwrap()),
+        }
+    }
+}
+
+impl usefulness::Constructors for RcPattern {
+    type Ty = RcType;
+
+    fn ctor(&self) -> Option<usefulness::Ctor> {
+        match *self.inner {
+            Pattern::Ann(ref pattern, _) => pattern.ctor(),
+            Pattern::Literal(_) => Some(usefulness::Ctor {
+                name: "<literal>".to_string(),
+                arity: 0,
+            }),
+            Pattern::Binder(_) => None,
+            Pattern::Record(ref fields) => Some(usefulness::Ctor {
+                name: "<record>".to_string(),
+                arity: fields.len(),
+            }),
+            Pattern::Tag(ref label, _) => Some(usefulness::Ctor {
+                name: label.clone(),
+                arity: 1,
+            }),
+        }
+    }
+
+    fn ctor_args(&self) -> Vec<RcPattern> {
+        match *self.inner {
+            Pattern::Ann(ref pattern, _) => pattern.ctor_args(),
+            Pattern::Literal(_) | Pattern::Binder(_) => vec![],
+            Pattern::Record(ref fields) => fields.iter().map(|&(_, ref p)| p.clone()).collect(),
+            Pattern::Tag(_, ref pattern) => vec![pattern.clone()],
+        }
+    }
+
+    fn from_ctor(ctor: &usefulness::Ctor, mut args: Vec<RcPattern>) -> RcPattern {
+        if ctor.name == "<literal>" {
+            // The set of literals is open - a witness here just means "some
+            // literal value", which we render as a fresh wildcard binder.
+            RcPattern::from(Pattern::Binder(Binder::user("_")))
+        } else if ctor.name == "<record>" {
+            RcPattern::from(Pattern::Record(
+                args.drain(..)
+                    .enumerate()
+                    .map(|(i, p)| (format!("_{}", i), p))
+                    .collect(),
+            ))
+        } else {
+            RcPattern::from(Pattern::Tag(
+                ctor.name.clone(),
+                args.into_iter().next().unwrap(),
+            ))
+        }
+    }
+
+    fn signature(ty: &RcType) -> Option<Vec<usefulness::Ctor>> {
+        match *ty.inner {
+            // A variant type has a closed, known set of tags, so a match
+            // that covers all of them is exhaustive without a wildcard.
+            Type::Variant(ref variants) => Some(
+                variants
+                    .iter()
+                    .map(|&(ref label, _)| usefulness::Ctor {
+                        name: label.clone(),
+                        arity: 1,
+                    })
+                    .collect(),
+            ),
+            // A record type has exactly one constructor (itself).
+            Type::Record(ref fields) => Some(vec![usefulness::Ctor {
+                name: "<record>".to_string(),
+                arity: fields.len(),
+            }]),
+            // Literals and function types have an open or absent constructor
+            // set, so a wildcard is always required to make a match
+            // exhaustive.
+            Type::Int | Type::Float | Type::String | Type::Arrow(_, _) | Type::Meta(_) => None,
+        }
+    }
+
+    fn ctor_tys(ctor: &usefulness::Ctor, ty: &RcType) -> Vec<RcType> {
+        match *ty.inner {
+            Type::Variant(ref variants) => match variants.get(&ctor.name) {
+                Some(payload_ty) => vec![payload_ty.clone()],
+                None => (0..ctor.arity)
+                    .map(|_| RcType::from(Type::Meta(FreeVar::from(GenId::fresh()))))
+                    .collect(),
+            },
+            Type::Record(ref fields) => fields.iter().map(|&(_, ref ty)| ty.clone()).collect(),
+            // We don't have a type to decompose here (eg. the scrutinee's
+            // type was never pinned down to a variant/record), so fall back
+            // to a fresh metavariable per sub-pattern.
+            _ => (0..ctor.arity)
+                .map(|_| RcType::from(Type::Meta(FreeVar::from(GenId::fresh()))))
+                .collect(),
+        }
+    }
+
+    fn wildcard() -> RcPattern {
+        RcPattern::from(Pattern::Binder(Binder::user("_")))
+    }
+}
 
 /// Synthesize the types of unambiguous patterns
 ///
 /// This function also returns a telescope that can be used to extend the typing
 /// context with additional bindings that the pattern introduces.
 pub fn check_pattern(
+    unifier: &mut Unifier,
     context: &Context,
     pattern: &RcPattern,
     expected_ty: &RcType,
@@ -376,38 +1174,41 @@ pub fn check_pattern(
             return Ok(Context::new().insert(free_var.clone(), expected_ty.clone()));
         },
         (&Pattern::Tag(ref label, ref pattern), &Type::Variant(ref variants)) => {
-            return match variants.iter().find(|&(l, _)| l == label) {
+            return match variants.get(label) {
                 None => Err(format!(
                     "variant type did not contain the label `{}`",
                     label
                 )),
-                Some(&(_, ref ty)) => check_pattern(context, pattern, ty),
+                Some(ty) => check_pattern(unifier, context, pattern, ty),
             };
         },
         (_, _) => {},
     }
 
-    let (inferred_ty, telescope) = infer_pattern(&context, pattern)?;
+    let (inferred_ty, telescope) = infer_pattern(unifier, &context, pattern)?;
 
-    // FIXME: allow out-of-order fields in records
-    if RcType::term_eq(&inferred_ty, expected_ty) {
-        Ok(telescope)
-    } else {
-        Err(format!(
+    unifier.unify(&inferred_ty, expected_ty).map_err(|_| {
+        format!(
             "type mismatch - found `{:?}` but expected `{:?}`",
-            inferred_ty, expected_ty
-        ))
-    }
+            unifier.zonk(&inferred_ty),
+            unifier.zonk(expected_ty),
+        )
+    })?;
+    Ok(telescope)
 }
 
 /// Check that a (potentially ambiguous) pattern conforms to a given type
 ///
 /// This function also returns a telescope that can be used to extend the typing
 /// context with additional bindings that the pattern introduces.
-pub fn infer_pattern(context: &Context, expr: &RcPattern) -> Result<(RcType, Context), String> {
+pub fn infer_pattern(
+    unifier: &mut Unifier,
+    context: &Context,
+    expr: &RcPattern,
+) -> Result<(RcType, Context), String> {
     match *expr.inner {
         Pattern::Ann(ref pattern, Embed(ref ty)) => {
-            let telescope = check_pattern(context, pattern, ty)?;
+            let telescope = check_pattern(unifier, context, pattern, ty)?;
             Ok((ty.clone(), telescope))
         },
         Pattern::Literal(Literal::Int(_)) => Ok((RcType::from(Type::Int), Context::new())),
@@ -415,23 +1216,419 @@ pub fn infer_pattern(context: &Context, expr: &RcPattern) -> Result<(RcType, Con
         Pattern::Literal(Literal::String(_)) => Ok((RcType::from(Type::String), Context::new())),
         Pattern::Binder(_) => Err("type annotations needed".to_string()),
         Pattern::Record(ref fields) => {
-            let mut telescope = Context::new();
+            // Thread the field patterns through a `Telescope` in key order,
+            // extending the context with each field's bindings before
+            // inferring the next. `RcType` cannot mention a pattern binder,
+            // so no field's annotation here depends on an earlier binder.
+            let labels: Vec<String> = fields.iter().map(|&(ref label, _)| label.clone()).collect();
+            let patterns: Vec<RcPattern> = fields.iter().map(|&(_, ref p)| p.clone()).collect();
 
-            let fields = fields
-                .iter()
-                .map(|&(ref label, ref pattern)| {
-                    let (pattern_ty, pattern_telescope) = infer_pattern(context, pattern)?;
-                    telescope.extend(pattern_telescope);
-                    Ok((label.clone(), pattern_ty))
-                })
-                .collect::<Result<_, String>>()?;
+            let mut telescope_context = context.clone();
+            let mut bindings = Context::new();
+            let mut field_tys = Vec::with_capacity(labels.len());
+            for (label, pattern) in labels.into_iter().zip(Telescope::new(patterns).unbind()) {
+                let (pattern_ty, pattern_telescope) =
+                    infer_pattern(unifier, &telescope_context, &pattern)?;
+                telescope_context = telescope_context + pattern_telescope.clone();
+                bindings = bindings + pattern_telescope;
+                field_tys.push((label, pattern_ty));
+            }
 
-            Ok((RcType::from(Type::Record(fields)), telescope))
+            Ok((RcType::from(Type::Record(field_tys.into_iter().collect())), bindings))
         },
         Pattern::Tag(_, _) => Err("type annotations needed".to_string()),
     }
 }
 
+/// Generators of well-scoped random terms, for property-testing the checker
+/// against laws like "`term_eq` is reflexive under renaming" or "subject
+/// reduction" without hand-writing example programs. Not test-only: a
+/// language built on top of this one can drive its own property tests with
+/// [`well_scoped`]/[`guided`] from its own `proptest!` blocks.
+///
+/// Scope-correctness is maintained by construction: a `Var::Free` is only
+/// ever produced by sampling from the `context` threaded into the current
+/// call, and a `Lam` clause only extends that `context` for the strategy
+/// building its own body. That alone would make `proptest`'s *default*
+/// shrinking safe, since it only shrinks a generated value towards other
+/// values the same strategy could have produced - but relying on that is
+/// exactly what bit `Telescope::unbind` elsewhere in this file: "the
+/// strategy that built it would never do X" is not the same guarantee as
+/// "X can't happen". So shrinking here does not touch the generated
+/// `RcExpr` tree at all. Generation instead consumes a flat `Vec<u32>`
+/// "tape" of choices (see [`Tape`]), and shrinking happens entirely on that
+/// tape via `proptest`'s own `Vec` shrinking (shorter tapes, smaller
+/// elements). Every tape - original or shrunk - is run back through the
+/// same [`expr_from_tape`] construction, which can only ever emit a
+/// well-scoped term; there is no path by which a shrunk candidate can
+/// escape that invariant, because nothing shrinks the term itself.
+// Nothing in this example's own `fn main` calls into `well_scoped`/`guided` -
+// only the tests at the bottom of this file do, and only under
+// `cargo test`. A downstream crate built on this one would call them from
+// its own property tests instead, which is the whole point of not gating
+// this module on `#[cfg(test)]` any more.
+#[allow(dead_code)]
+mod gen {
+    use proptest::prelude::*;
+    use proptest::strategy::BoxedStrategy;
+
+    use super::{Binder, Context, Embed, Expr, Literal, Pattern, RcExpr, RcPattern, RcType, Scope, Type, Var};
+
+    /// Bounds on the shape of generated terms. Recursive grammars need an
+    /// explicit depth cutoff so a tape doesn't have to be infinite.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Params {
+        pub max_depth: u32,
+    }
+
+    impl Default for Params {
+        fn default() -> Params {
+            Params { max_depth: 3 }
+        }
+    }
+
+    /// A cursor over a tape of arbitrary `u32` choices, consumed in order by
+    /// [`expr_from_tape`] and friends to make each branching decision.
+    /// Running out of tape yields `0` forever rather than panicking, so a
+    /// shrunk (shorter) tape always still produces a term - just the
+    /// smallest one reachable from that point, since `0` always selects a
+    /// leaf-most or depth-reducing choice.
+    struct Tape<'a> {
+        values: &'a [u32],
+        pos: usize,
+    }
+
+    impl<'a> Tape<'a> {
+        fn next_u32(&mut self) -> u32 {
+            let value = self.values.get(self.pos).copied().unwrap_or(0);
+            self.pos += 1;
+            value
+        }
+
+        /// Picks an index in `0..n`, or `0` if `n` is `0`.
+        fn choose(&mut self, n: u32) -> u32 {
+            if n == 0 {
+                0
+            } else {
+                self.next_u32() % n
+            }
+        }
+    }
+
+    fn hint_from_tape(tape: &mut Tape) -> String {
+        const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+        let letter = ALPHABET[tape.choose(ALPHABET.len() as u32) as usize] as char;
+        letter.to_string()
+    }
+
+    fn literal_from_tape(tape: &mut Tape) -> Literal {
+        match tape.choose(3) {
+            0 => Literal::Int(tape.next_u32() as i32),
+            1 => Literal::Float(tape.next_u32() as f32),
+            _ => Literal::String(format!("s{}", tape.choose(1000))),
+        }
+    }
+
+    fn ty_from_tape(tape: &mut Tape, depth: u32) -> RcType {
+        let leaf_choices = 3;
+        if depth == 0 {
+            return match tape.choose(leaf_choices) {
+                0 => RcType::from(Type::Int),
+                1 => RcType::from(Type::Float),
+                _ => RcType::from(Type::String),
+            };
+        }
+        match tape.choose(leaf_choices + 1) {
+            0 => RcType::from(Type::Int),
+            1 => RcType::from(Type::Float),
+            2 => RcType::from(Type::String),
+            _ => RcType::from(Type::Arrow(
+                ty_from_tape(tape, depth - 1),
+                ty_from_tape(tape, depth - 1),
+            )),
+        }
+    }
+
+    /// Builds a well-scoped expression by consuming choices from `tape`: a
+    /// `Var::Free` is only ever chosen from `context`'s existing keys, and a
+    /// `Lam`'s body is only built against `context` extended with its own
+    /// fresh binder - so no choice of tape, however it was arrived at, can
+    /// produce a `Var::Free` that escapes the `Scope` it was bound in.
+    fn expr_from_tape(tape: &mut Tape, context: &Context, depth: u32) -> RcExpr {
+        let vars: Vec<Var<String>> = context.keys().cloned().map(Var::Free).collect();
+        let leaf_choices = if vars.is_empty() { 1 } else { 2 };
+
+        if depth == 0 {
+            return match tape.choose(leaf_choices) {
+                0 => RcExpr::from(Expr::Literal(literal_from_tape(tape))),
+                _ => RcExpr::from(Expr::Var(vars[tape.choose(vars.len() as u32) as usize].clone())),
+            };
+        }
+
+        match tape.choose(leaf_choices + 2) {
+            0 => RcExpr::from(Expr::Literal(literal_from_tape(tape))),
+            1 if !vars.is_empty() => {
+                RcExpr::from(Expr::Var(vars[tape.choose(vars.len() as u32) as usize].clone()))
+            },
+            choice if choice == leaf_choices => {
+                let binder = Binder::user(hint_from_tape(tape));
+                let param_ty = ty_from_tape(tape, depth - 1);
+                let mut body_context = context.clone();
+                body_context.insert(binder.0.clone(), param_ty.clone());
+                let body = expr_from_tape(tape, &body_context, depth - 1);
+                RcExpr::from(Expr::Lam(Scope::new(
+                    RcPattern::from(Pattern::Ann(
+                        RcPattern::from(Pattern::Binder(binder)),
+                        Embed(param_ty),
+                    )),
+                    body,
+                )))
+            },
+            _ => {
+                let fun = expr_from_tape(tape, context, depth - 1);
+                let arg = expr_from_tape(tape, context, depth - 1);
+                RcExpr::from(Expr::App(fun, arg))
+            },
+        }
+    }
+
+    /// A well-scoped expression strategy: see the module documentation for
+    /// why shrinking is safe here even though it's driven by `proptest`'s
+    /// ordinary `Vec<u32>` shrinking rather than a hand-rolled `ValueTree`.
+    pub fn well_scoped(context: &Context, params: Params) -> BoxedStrategy<RcExpr> {
+        let context = context.clone();
+        proptest::collection::vec(any::<u32>(), 0..64)
+            .prop_map(move |values| expr_from_tape(&mut Tape { values: &values, pos: 0 }, &context, params.max_depth))
+            .boxed()
+    }
+
+    /// Hook for driving generation with a language's own typing rules. This
+    /// default takes no expected type and just forwards to [`well_scoped`],
+    /// so the terms it produces are well-scoped but not necessarily
+    /// well-typed.
+    pub fn guided(context: &Context, params: Params) -> BoxedStrategy<RcExpr> {
+        well_scoped(context, params)
+    }
+}
+
+#[cfg(test)]
+proptest! {
+    /// `term_eq` must hold of every well-scoped term against itself - the
+    /// property the request asks for directly, and a sanity check that
+    /// `gen::well_scoped` never hands back a term that isn't even
+    /// consistently comparable to itself (eg. one with a dangling index).
+    #[test]
+    fn well_scoped_term_eq_is_reflexive(
+        expr in gen::well_scoped(&Context::new(), gen::Params::default()),
+    ) {
+        prop_assert!(RcExpr::term_eq(&expr, &expr));
+    }
+
+    /// Substituting a fresh free variable that cannot appear in a generated
+    /// term is a no-op - another way of saying `gen::well_scoped` never
+    /// leaks a `Var::Free` it didn't introduce through `context`.
+    #[test]
+    fn well_scoped_substs_with_unused_var_is_noop(
+        expr in gen::well_scoped(&Context::new(), gen::Params::default()),
+    ) {
+        let unused = FreeVar::user("unused".to_string()).freshen();
+        let replacement = RcExpr::from(Expr::Literal(Literal::Int(0)));
+        prop_assert!(RcExpr::term_eq(
+            &expr.substs(&[(unused, replacement)]),
+            &expr,
+        ));
+    }
+}
+
+#[test]
+fn test_well_scoped_shrinks_to_well_scoped() {
+    // `gen::well_scoped` shrinks a generated term by shrinking the `Vec<u32>`
+    // tape that drove its construction, not the term itself - so every
+    // candidate `proptest` tries while shrinking, including the empty tape,
+    // must still come back out of the *same* generator and be well-scoped by
+    // construction. An empty tape is the smallest possible input: it must
+    // still produce a term, not panic on a starved cursor.
+    use proptest::strategy::{Strategy, ValueTree};
+
+    let mut runner = proptest::test_runner::TestRunner::default();
+    let strategy = gen::well_scoped(&Context::new(), gen::Params::default());
+    let mut tree = strategy.new_tree(&mut runner).unwrap();
+    loop {
+        let expr = tree.current();
+        assert!(RcExpr::term_eq(&expr, &expr));
+        if !tree.simplify() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_telescope_unbind_preserves_order() {
+    // { x = 1, y = x } - y's embedded value refers to x by name, so unbind
+    // must hand that reference back as x's (freshened) free variable, not a
+    // dangling `Var::Bound` pointing at a scope depth inside the telescope.
+    let telescope = Telescope::new(vec![
+        (
+            Binder::user("x"),
+            Embed(RcExpr::from(Expr::Literal(Literal::Int(1)))),
+        ),
+        (
+            Binder::user("y"),
+            Embed(RcExpr::from(Expr::Var(Var::user("x")))),
+        ),
+    ]);
+
+    let entries = telescope.unbind();
+    assert_eq!(entries.len(), 2);
+
+    let (ref x_binder, Embed(_)) = entries[0];
+    let (_, Embed(ref y_value)) = entries[1];
+    match *y_value.inner {
+        Expr::Var(Var::Free(ref free_var)) => assert_eq!(free_var, &x_binder.0),
+        ref other => panic!("expected a free variable referring to `x`, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_pretty_restores_binder_hint() {
+    // expr = (\x -> x)
+    let expr = RcExpr::from(Expr::Lam(Scope::new(
+        RcPattern::from(Pattern::Binder(Binder::user("x"))),
+        RcExpr::from(Expr::Var(Var::user("x"))),
+    )));
+    assert_eq!(expr.to_doc().to_string(), "\\x -> x");
+}
+
+#[test]
+fn test_substs_var() {
+    // (x).substs([x -> 1]) = 1
+    let free_x = FreeVar::user("x".to_string()).freshen();
+    let expr = RcExpr::from(Expr::Var(Var::Free(free_x.clone())));
+    let replacement = RcExpr::from(Expr::Literal(Literal::Int(1)));
+    assert_term_eq!(expr.substs(&[(free_x, replacement.clone())]), replacement);
+}
+
+#[test]
+fn test_unify_meta_with_itself() {
+    // The same unbound metavariable unified with itself should succeed
+    // trivially, rather than being rejected by the occurs check.
+    let mut unifier = Unifier::new();
+    let meta = unifier.fresh_meta();
+    assert!(unifier.unify(&meta, &meta).is_ok());
+}
+
+#[test]
+fn test_unify_occurs_check() {
+    // ?a unified with (?a -> Int) would require an infinite type; the
+    // occurs check must reject it instead of looping or solving ?a to a
+    // type that contains itself.
+    let mut unifier = Unifier::new();
+    let meta = unifier.fresh_meta();
+    let cyclic_ty = RcType::from(Type::Arrow(meta.clone(), RcType::from(Type::Int)));
+    match unifier.unify(&meta, &cyclic_ty) {
+        Err(unify::UnifyError::Occurs(_, _)) => {},
+        other => panic!("expected an occurs-check error, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_check_exhaustive_variant_case() {
+    // case (a 1 : <a : Int, b : Int>) { a x -> x } is missing the `b` arm.
+    let variant_ty = || {
+        RcType::from(Type::Variant(Unordered::new(vec![
+            (String::from("a"), RcType::from(Type::Int)),
+            (String::from("b"), RcType::from(Type::Int)),
+        ])))
+    };
+    let scrutinee = |ty: RcType| {
+        RcExpr::from(Expr::Ann(
+            RcExpr::from(Expr::Tag(
+                String::from("a"),
+                RcExpr::from(Expr::Literal(Literal::Int(1))),
+            )),
+            ty,
+        ))
+    };
+    let clause = |label: &str| {
+        Scope::new(
+            RcPattern::from(Pattern::Tag(
+                label.to_string(),
+                RcPattern::from(Pattern::Binder(Binder::user("x"))),
+            )),
+            RcExpr::from(Expr::Var(Var::user("x"))),
+        )
+    };
+
+    let non_exhaustive = RcExpr::from(Expr::Case(scrutinee(variant_ty()), vec![clause("a")]));
+    let result = check_expr(
+        &mut Unifier::new(),
+        &Context::new(),
+        &non_exhaustive,
+        &RcType::from(Type::Int),
+    );
+    assert!(result.unwrap_err().contains("non-exhaustive"));
+
+    let exhaustive = RcExpr::from(Expr::Case(
+        scrutinee(variant_ty()),
+        vec![clause("a"), clause("b")],
+    ));
+    assert!(check_expr(
+        &mut Unifier::new(),
+        &Context::new(),
+        &exhaustive,
+        &RcType::from(Type::Int),
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_check_exhaustive_record_witness_is_record_shaped() {
+    // case ({a = 1, b = 2} : {a : Int, b : Int}) { {a = x, b = 0} -> x } can
+    // never be exhaustive - `b`'s literal sub-pattern is matched against an
+    // open constructor set - so the witness itself should be shaped like the
+    // record it failed to cover (one placeholder per field), not a single
+    // bare wildcard that throws the field structure away.
+    let record_ty = RcType::from(Type::Record(Unordered::new(vec![
+        (String::from("a"), RcType::from(Type::Int)),
+        (String::from("b"), RcType::from(Type::Int)),
+    ])));
+    let scrutinee = RcExpr::from(Expr::Ann(
+        RcExpr::from(Expr::Record(Unordered::new(vec![
+            (String::from("a"), RcExpr::from(Expr::Literal(Literal::Int(1)))),
+            (String::from("b"), RcExpr::from(Expr::Literal(Literal::Int(2)))),
+        ]))),
+        record_ty,
+    ));
+    let clause = Scope::new(
+        RcPattern::from(Pattern::Record(Unordered::new(vec![
+            (
+                String::from("a"),
+                RcPattern::from(Pattern::Ann(
+                    RcPattern::from(Pattern::Binder(Binder::user("x"))),
+                    Embed(RcType::from(Type::Int)),
+                )),
+            ),
+            (String::from("b"), RcPattern::from(Pattern::Literal(Literal::Int(0)))),
+        ]))),
+        RcExpr::from(Expr::Var(Var::user("x"))),
+    );
+
+    let non_exhaustive = RcExpr::from(Expr::Case(scrutinee, vec![clause]));
+    let err = check_expr(
+        &mut Unifier::new(),
+        &Context::new(),
+        &non_exhaustive,
+        &RcType::from(Type::Int),
+    )
+    .unwrap_err();
+    assert!(err.contains("non-exhaustive"));
+    assert!(
+        err.contains("_0") && err.contains("_1"),
+        "expected a record-shaped witness with one placeholder per field, got: {}",
+        err,
+    );
+}
+
 #[test]
 fn test_infer_expr() {
     // expr = (\x : Int -> x)
@@ -444,7 +1641,7 @@ fn test_infer_expr() {
     )));
 
     assert_term_eq!(
-        infer_expr(&Context::new(), &expr).unwrap(),
+        infer_expr(&mut Unifier::new(), &Context::new(), &expr).unwrap(),
         RcType::from(Type::Arrow(
             RcType::from(Type::Int),
             RcType::from(Type::Int)
@@ -470,7 +1667,7 @@ fn test_infer_app_expr() {
     ));
 
     assert_term_eq!(
-        infer_expr(&Context::new(), &expr).unwrap(),
+        infer_expr(&mut Unifier::new(), &Context::new(), &expr).unwrap(),
         RcType::from(Type::Int),
     );
 }
@@ -479,7 +1676,7 @@ fn test_infer_app_expr() {
 fn test_infer_expr_record1() {
     // expr = \{ x = a : Int, y = b : String } -> b
     let expr = RcExpr::from(Expr::Lam(Scope::new(
-        RcPattern::from(Pattern::Record(vec![
+        RcPattern::from(Pattern::Record(Unordered::new(vec![
             (
                 String::from("x"),
                 RcPattern::from(Pattern::Ann(
@@ -494,17 +1691,17 @@ fn test_infer_expr_record1() {
                     Embed(RcType::from(Type::String)),
                 )),
             ),
-        ])),
+        ]))),
         RcExpr::from(Expr::Var(Var::user("b"))),
     )));
 
     assert_term_eq!(
-        infer_expr(&Context::new(), &expr).unwrap(),
+        infer_expr(&mut Unifier::new(), &Context::new(), &expr).unwrap(),
         RcType::from(Type::Arrow(
-            RcType::from(Type::Record(vec![
+            RcType::from(Type::Record(Unordered::new(vec![
                 (String::from("x"), RcType::from(Type::Int)),
                 (String::from("y"), RcType::from(Type::String)),
-            ])),
+            ]))),
             RcType::from(Type::String),
         )),
     );
@@ -514,7 +1711,7 @@ fn test_infer_expr_record1() {
 fn test_infer_expr_record2() {
     // expr = \{ x = a : Int, y = b : String, z = c : Float } -> { x = a, y = b, z = c }
     let expr = RcExpr::from(Expr::Lam(Scope::new(
-        RcPattern::from(Pattern::Record(vec![
+        RcPattern::from(Pattern::Record(Unordered::new(vec![
             (
                 String::from("x"),
                 RcPattern::from(Pattern::Ann(
@@ -536,31 +1733,40 @@ fn test_infer_expr_record2() {
                     Embed(RcType::from(Type::Float)),
                 )),
             ),
-        ])),
-        RcExpr::from(Expr::Record(vec![
+        ]))),
+        RcExpr::from(Expr::Record(Unordered::new(vec![
             (String::from("x"), RcExpr::from(Expr::Var(Var::user("a")))),
             (String::from("y"), RcExpr::from(Expr::Var(Var::user("b")))),
             (String::from("z"), RcExpr::from(Expr::Var(Var::user("c")))),
-        ])),
+        ]))),
     )));
 
     assert_term_eq!(
-        infer_expr(&Context::new(), &expr).unwrap(),
+        infer_expr(&mut Unifier::new(), &Context::new(), &expr).unwrap(),
         RcType::from(Type::Arrow(
-            RcType::from(Type::Record(vec![
+            RcType::from(Type::Record(Unordered::new(vec![
                 (String::from("x"), RcType::from(Type::Int)),
                 (String::from("y"), RcType::from(Type::String)),
                 (String::from("z"), RcType::from(Type::Float)),
-            ])),
-            RcType::from(Type::Record(vec![
+            ]))),
+            RcType::from(Type::Record(Unordered::new(vec![
                 (String::from("x"), RcType::from(Type::Int)),
                 (String::from("y"), RcType::from(Type::String)),
                 (String::from("z"), RcType::from(Type::Float)),
-            ])),
+            ]))),
         )),
     );
 }
 
+#[test]
+#[should_panic(expected = "duplicate key")]
+fn test_unordered_duplicate_key_panics() {
+    Unordered::new(vec![
+        (String::from("a"), RcType::from(Type::Int)),
+        (String::from("a"), RcType::from(Type::String)),
+    ]);
+}
+
 // TODO: Use property testing for this!
 // http://janmidtgaard.dk/papers/Midtgaard-al%3AICFP17-full.pdf
 